@@ -0,0 +1,177 @@
+use anyhow::{Context, Result, bail};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Mutex;
+use time::{Duration, OffsetDateTime};
+use tracing::debug;
+
+/// The credentials action-hero will present to the GitHub API, resolved from
+/// the environment. A GitHub App is preferred when fully configured because it
+/// lets the tool run unattended across many repositories; otherwise we fall
+/// back to a personal access token as before.
+pub enum Credentials {
+    /// A personal access token supplied via `GITHUB_TOKEN`.
+    Pat(String),
+    /// A GitHub App installation, whose short-lived tokens we mint on demand.
+    App(AppConfig),
+}
+
+/// The three values needed to authenticate as a GitHub App installation.
+pub struct AppConfig {
+    app_id: String,
+    private_key: String,
+    installation_id: String,
+}
+
+impl Credentials {
+    /// Resolve credentials from the environment. When `GITHUB_APP_ID`,
+    /// `GITHUB_APP_PRIVATE_KEY`, and `GITHUB_APP_INSTALLATION_ID` are all set
+    /// we authenticate as that App installation; otherwise we require
+    /// `GITHUB_TOKEN`.
+    pub fn from_env() -> Result<Self> {
+        let app_id = std::env::var("GITHUB_APP_ID").ok();
+        let private_key = std::env::var("GITHUB_APP_PRIVATE_KEY").ok();
+        let installation_id = std::env::var("GITHUB_APP_INSTALLATION_ID").ok();
+
+        if let (Some(app_id), Some(private_key), Some(installation_id)) =
+            (app_id, private_key, installation_id)
+        {
+            let private_key = load_private_key(&private_key)?;
+            return Ok(Credentials::App(AppConfig {
+                app_id,
+                private_key,
+                installation_id,
+            }));
+        }
+
+        let token = std::env::var("GITHUB_TOKEN")
+            .context("neither GitHub App variables nor GITHUB_TOKEN are set")?;
+        Ok(Credentials::Pat(token))
+    }
+}
+
+/// `GITHUB_APP_PRIVATE_KEY` may be either the PEM contents directly or a path
+/// to a `.pem` file on disk; distinguish the two by the PEM armour header.
+fn load_private_key(value: &str) -> Result<String> {
+    if value.trim_start().starts_with("-----BEGIN") {
+        Ok(value.to_owned())
+    } else {
+        std::fs::read_to_string(value)
+            .with_context(|| format!("failed to read GitHub App private key from {}", value))
+    }
+}
+
+/// Claims for the RS256 JWT GitHub requires when exchanging App credentials
+/// for an installation token.
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+/// Build a short-lived RS256 JWT asserting the App's identity. GitHub rejects
+/// tokens whose `iat` is in the future relative to its own clock, so we
+/// back-date `iat` by 60 seconds and cap the lifetime at the permitted 10
+/// minutes.
+fn build_app_jwt(config: &AppConfig) -> Result<String> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let claims = Claims {
+        iat: now - 60,
+        exp: now + 600,
+        iss: config.app_id.clone(),
+    };
+    let key = EncodingKey::from_rsa_pem(config.private_key.as_bytes())
+        .context("GitHub App private key is not a valid RSA PEM")?;
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+        .context("failed to sign GitHub App JWT")
+}
+
+/// An installation token together with the instant it expires.
+struct CachedToken {
+    value: String,
+    expires_at: OffsetDateTime,
+}
+
+/// Resolves and caches the `Authorization: Bearer` token presented on every
+/// request. PATs are returned verbatim; App installation tokens are minted via
+/// the installations endpoint and transparently refreshed when they are within
+/// a minute of their one-hour expiry.
+pub struct TokenProvider {
+    credentials: Credentials,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl TokenProvider {
+    pub fn new(credentials: Credentials) -> Self {
+        TokenProvider {
+            credentials,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Return a currently-valid bearer token, minting or refreshing an
+    /// installation token as needed. `http` is a bare client used only for the
+    /// token exchange so it is free of the default `Authorization` header.
+    pub async fn bearer_token(&self, http: &reqwest::Client) -> Result<String> {
+        let config = match &self.credentials {
+            Credentials::Pat(token) => return Ok(token.clone()),
+            Credentials::App(config) => config,
+        };
+
+        {
+            let cached = self.cached.lock().unwrap();
+            if let Some(token) = cached.as_ref() {
+                if token.expires_at - OffsetDateTime::now_utc() > Duration::minutes(1) {
+                    return Ok(token.value.clone());
+                }
+            }
+        }
+
+        let (value, expires_at) = self.mint_installation_token(http, config).await?;
+        let result = value.clone();
+        *self.cached.lock().unwrap() = Some(CachedToken { value, expires_at });
+        Ok(result)
+    }
+
+    async fn mint_installation_token(
+        &self,
+        http: &reqwest::Client,
+        config: &AppConfig,
+    ) -> Result<(String, OffsetDateTime)> {
+        let jwt = build_app_jwt(config)?;
+        let url = format!(
+            "https://api.github.com/app/installations/{}/access_tokens",
+            config.installation_id
+        );
+        debug!(?url, "minting GitHub App installation token");
+
+        let body: Value = http
+            .post(url)
+            .header("Authorization", format!("Bearer {}", jwt))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", format!("action-hero/{}", crate::VERSION))
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let token = match body["token"].as_str() {
+            Some(token) => token.to_owned(),
+            None => bail!("installation token response did not contain a token"),
+        };
+        let expires_at = body["expires_at"]
+            .as_str()
+            .and_then(|raw| {
+                OffsetDateTime::parse(raw, &time::format_description::well_known::Rfc3339).ok()
+            })
+            // GitHub installation tokens live one hour; assume the minimum if
+            // the field is ever missing so we refresh conservatively.
+            .unwrap_or_else(|| OffsetDateTime::now_utc() + Duration::hours(1));
+
+        Ok((token, expires_at))
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+
+/// A persistent store of every run, job, and step action-hero has retrieved,
+/// backed by SQLite via `rusqlite`. Runs are keyed by their GitHub run id and
+/// jobs by their job id, so repeated invocations can fetch only the delta and
+/// `--offline` mode can rebuild traces without touching the API at all. Runs
+/// also carry their owner/repository/workflow so the offline and regression
+/// queries stay scoped to the repo and workflow asked for rather than mixing
+/// every repo cached in the same file.
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Open (creating if necessary) the database at `path` and ensure the
+    /// schema exists. Safe to call on every invocation.
+    pub fn open(path: &str) -> Result<Database> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open database at {}", path))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS runs (
+                id          INTEGER PRIMARY KEY,
+                owner       TEXT NOT NULL,
+                repository  TEXT NOT NULL,
+                workflow    TEXT NOT NULL,
+                name        TEXT,
+                created_at  TEXT,
+                updated_at  TEXT,
+                conclusion  TEXT,
+                payload     TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS runs_scope ON runs(owner, repository, workflow);
+            CREATE TABLE IF NOT EXISTS jobs (
+                id          INTEGER PRIMARY KEY,
+                run_id      INTEGER NOT NULL REFERENCES runs(id),
+                name        TEXT,
+                started_at  TEXT,
+                completed_at TEXT,
+                conclusion  TEXT,
+                payload     TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_run_id ON jobs(run_id);",
+        )
+        .context("failed to create database schema")?;
+
+        Ok(Database { conn })
+    }
+
+    /// Whether a run with `run_id` is already held in the cache. Used to decide
+    /// per run whether the delta needs fetching, rather than trusting a single
+    /// `MAX(id)` high-water mark that a lower-id filtered run could slip under.
+    pub fn contains_run(&self, run_id: i64) -> Result<bool> {
+        let present: Option<i64> = self
+            .conn
+            .query_row("SELECT 1 FROM runs WHERE id = ?1", params![run_id], |row| row.get(0))
+            .optional()
+            .context("failed to check whether run is cached")?;
+        Ok(present.is_some())
+    }
+
+    /// Run ids held in the cache for the given owner/repository/workflow, most
+    /// recent first.
+    pub fn run_ids(&self, owner: &str, repository: &str, workflow: &str) -> Result<Vec<i64>> {
+        let mut statement = self.conn.prepare(
+            "SELECT id FROM runs
+             WHERE owner = ?1 AND repository = ?2 AND workflow = ?3
+             ORDER BY id DESC",
+        )?;
+        let ids = statement
+            .query_map(params![owner, repository, workflow], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .context("failed to read run ids")?;
+        Ok(ids)
+    }
+
+    /// Insert (or replace) a run together with its jobs and their steps. Steps
+    /// ride along inside each job's payload, matching the shape returned by the
+    /// jobs endpoint, so a later `load_run` hands callers exactly what the API
+    /// would have.
+    pub fn record_run(
+        &self,
+        owner: &str,
+        repository: &str,
+        workflow: &str,
+        run: &Value,
+        jobs: &[Value],
+    ) -> Result<()> {
+        let run_id = run["id"]
+            .as_i64()
+            .context("run is missing a numeric id")?;
+
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO runs
+                    (id, owner, repository, workflow, name, created_at, updated_at, conclusion, payload)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    run_id,
+                    owner,
+                    repository,
+                    workflow,
+                    run["name"].as_str(),
+                    run["created_at"].as_str(),
+                    run["updated_at"].as_str(),
+                    run["conclusion"].as_str(),
+                    run.to_string(),
+                ],
+            )
+            .context("failed to insert run")?;
+
+        for job in jobs {
+            let job_id = job["id"]
+                .as_i64()
+                .context("job is missing a numeric id")?;
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO jobs
+                        (id, run_id, name, started_at, completed_at, conclusion, payload)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        job_id,
+                        run_id,
+                        job["name"].as_str(),
+                        job["started_at"].as_str(),
+                        job["completed_at"].as_str(),
+                        job["conclusion"].as_str(),
+                        job.to_string(),
+                    ],
+                )
+                .context("failed to insert job")?;
+        }
+
+        Ok(())
+    }
+
+    /// Reconstruct a cached run and its jobs, or `None` if the run is unknown.
+    pub fn load_run(&self, run_id: i64) -> Result<Option<(Value, Vec<Value>)>> {
+        let run: Option<String> = self
+            .conn
+            .query_row("SELECT payload FROM runs WHERE id = ?1", params![run_id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .context("failed to load run payload")?;
+
+        let run = match run {
+            Some(payload) => serde_json::from_str::<Value>(&payload)?,
+            None => return Ok(None),
+        };
+
+        let mut statement = self
+            .conn
+            .prepare("SELECT payload FROM jobs WHERE run_id = ?1 ORDER BY id ASC")?;
+        let jobs = statement
+            .query_map(params![run_id], |row| row.get::<_, String>(0))?
+            .map(|payload| Ok(serde_json::from_str::<Value>(&payload?)?))
+            .collect::<Result<Vec<Value>>>()?;
+
+        Ok(Some((run, jobs)))
+    }
+}
@@ -1,58 +1,170 @@
 use anyhow::Result;
 use clap::{Arg, ArgAction, Command};
-use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::header::HeaderMap;
 use serde_json::Value;
+use std::sync::Arc;
 use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use tracing::debug;
 use tracing_subscriber;
 
+mod auth;
+mod dbctx;
+mod regression;
+mod serve;
+mod telemetry;
+
 const VERSION: &str = concat!("v", env!("CARGO_PKG_VERSION"));
 
 /// A struct holding the configuration being used to retrieve information from
 /// GitHub's API.
 struct API {
     client: reqwest::Client,
+    provider: Arc<auth::TokenProvider>,
     owner: String,
     repository: String,
     workflow: String,
 }
 
-async fn retrieve_workflow_runs(api: &API) -> Result<Vec<String>> {
-    // use token to retrieve runs for the given workflow from GitHub API
+impl API {
+    /// Issue a GET against `url` carrying a freshly-resolved bearer token.
+    /// Routing every request through here means a GitHub App installation
+    /// token that has expired (they live only an hour) is transparently
+    /// refreshed rather than frozen into the client's default headers for its
+    /// whole lifetime, which matters for the long-lived `serve` path.
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let token = self
+            .provider
+            .bearer_token(&self.client)
+            .await?;
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+        Ok(response)
+    }
+}
+
+/// Options narrowing and bounding the set of workflow runs retrieved. Each
+/// filter field maps onto the corresponding GitHub list-runs query parameter;
+/// `None` leaves that parameter off entirely. `limit` is not sent to GitHub: it
+/// caps the total number of runs collected across pages.
+#[derive(Default)]
+struct RunQuery {
+    branch: Option<String>,
+    event: Option<String>,
+    status: Option<String>,
+    limit: usize,
+}
 
-    let url = format!(
-        "https://api.github.com/repos/{}/{}/actions/workflows/{}/runs?per_page=10&page=1",
+async fn retrieve_workflow_runs(api: &API, query: &RunQuery) -> Result<Vec<String>> {
+    // use token to retrieve runs for the given workflow from GitHub API,
+    // following the Link header's rel="next" until we have collected enough
+    // runs or the pages are exhausted.
+
+    // a limit of zero asks for no runs at all; return before issuing any
+    // request so the first page isn't mistaken for "enough".
+    if query.limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    // request the largest page GitHub allows so we make as few round-trips as
+    // possible, then translate each filter onto a query parameter.
+    let mut params: Vec<(&str, String)> = vec![("per_page", "100".to_string())];
+    if let Some(branch) = &query.branch {
+        params.push(("branch", branch.clone()));
+    }
+    if let Some(event) = &query.event {
+        params.push(("event", event.clone()));
+    }
+    if let Some(status) = &query.status {
+        // the GitHub `status` parameter accepts both check statuses
+        // (e.g. "completed") and conclusions (e.g. "failure").
+        params.push(("status", status.clone()));
+    }
+
+    let base = format!(
+        "https://api.github.com/repos/{}/{}/actions/workflows/{}/runs",
         api.owner, api.repository, api.workflow
     );
-    debug!(?url);
+    let mut next: Option<String> = Some(
+        reqwest::Url::parse_with_params(&base, &params)
+            .expect("Failed to build workflow runs URL")
+            .to_string(),
+    );
 
-    let response = api
-        .client
-        .get(&url)
-        .send()
-        .await?;
+    let mut runs: Vec<String> = Vec::new();
 
-    // retrieve the run ID of the most recent 10 runs
-    let body: Value = response
-        .json()
-        .await?;
+    while let Some(url) = next {
+        debug!(?url);
 
-    let runs: Vec<String> = body["workflow_runs"]
-        .as_array()
-        .expect("Expected workflow_runs to be an array")
-        .iter()
-        .take(10)
-        .map(|workflow_run| {
-            workflow_run["id"]
+        let response = api
+            .get(&url)
+            .await?;
+
+        next = next_page_url(&response);
+
+        let body: Value = response
+            .json()
+            .await?;
+
+        for workflow_run in body["workflow_runs"]
+            .as_array()
+            .expect("Expected workflow_runs to be an array")
+        {
+            let id = workflow_run["id"]
                 .as_i64()
                 .expect("Expected run ID to be present and non-empty")
-                .to_string()
-        })
-        .collect();
+                .to_string();
+            runs.push(id);
+
+            if runs.len() >= query.limit {
+                return Ok(runs);
+            }
+        }
+    }
 
     Ok(runs)
 }
 
+/// Extract the `rel="next"` URL from a response's `Link` header, if present.
+/// GitHub paginates via this header rather than a predictable page count.
+fn next_page_url(response: &reqwest::Response) -> Option<String> {
+    let header = response.headers().get(reqwest::header::LINK)?.to_str().ok()?;
+
+    // the Link header is a comma-separated list of `<url>; rel="name"` entries.
+    for entry in header.split(',') {
+        let mut parts = entry.split(';');
+        let url = parts.next()?.trim().trim_start_matches('<').trim_end_matches('>');
+        if parts.any(|attr| attr.trim() == "rel=\"next\"") {
+            return Some(url.to_string());
+        }
+    }
+
+    None
+}
+
+async fn retrieve_run(api: &API, run_id: &str) -> Result<Value> {
+    // fetch the run object itself for its created_at/updated_at timestamps,
+    // which bound the root span we emit for the run.
+
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/actions/runs/{}",
+        api.owner, api.repository, run_id
+    );
+
+    debug!(?url);
+
+    let run = api
+        .get(&url)
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+
+    Ok(run)
+}
+
 async fn retrieve_run_jobs(api: &API, run_id: &str) -> Result<Vec<Value>> {
     let url = format!(
         "https://api.github.com/repos/{}/{}/actions/runs/{}/jobs",
@@ -62,9 +174,7 @@ async fn retrieve_run_jobs(api: &API, run_id: &str) -> Result<Vec<Value>> {
     debug!(?url);
 
     let response = api
-        .client
-        .get(url)
-        .send()
+        .get(&url)
         .await?;
 
     let body = response
@@ -117,20 +227,18 @@ fn display_job_steps(jobs: &Vec<serde_json::Value>) {
     }
 }
 
-fn setup_api_client() -> Result<reqwest::Client> {
-    // get GITHUB_TOKEN value from environment variable
-    let token = std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN environment variable not set");
+async fn setup_api_client() -> Result<(reqwest::Client, Arc<auth::TokenProvider>)> {
+    // Resolve credentials from the environment: a GitHub App installation when
+    // fully configured, otherwise a GITHUB_TOKEN PAT as before.
+    let credentials = auth::Credentials::from_env()?;
+    let provider = Arc::new(auth::TokenProvider::new(credentials));
 
     // Initialize a request Client as we will be making many requests of
-    // the GitHub API.
+    // the GitHub API. The Authorization header is deliberately *not* a default
+    // header: it is resolved per request (see `API::get`) so a GitHub App
+    // installation token is refreshed as it nears expiry rather than baked in.
     let mut headers = HeaderMap::new();
 
-    let mut auth: HeaderValue = format!("Bearer {}", token)
-        .parse()
-        .unwrap();
-    auth.set_sensitive(true);
-    headers.insert("Authorization", auth);
-
     headers.insert(
         "Accept",
         "application/vnd.github+json"
@@ -155,7 +263,7 @@ fn setup_api_client() -> Result<reqwest::Client> {
         .default_headers(headers)
         .build()?;
 
-    Ok(client)
+    Ok((client, provider))
 }
 
 #[tokio::main]
@@ -171,6 +279,18 @@ async fn main() -> Result<()> {
             .disable_help_subcommand(true)
             .disable_help_flag(true)
             .disable_version_flag(true)
+            // the `serve` subcommand reads owner/repo from webhook payloads, so
+            // the positional repository/workflow arguments don't apply to it.
+            .subcommand_negates_reqs(true)
+            .subcommand(
+                Command::new("serve")
+                    .about("Run a webhook listener that traces runs as they complete")
+                    .arg(
+                        Arg::new("listen")
+                            .long("listen")
+                            .action(ArgAction::Set)
+                            .default_value("127.0.0.1:3000")
+                            .help("Address to bind the webhook listener to")))
             .arg(
                 Arg::new("help")
                     .long("help")
@@ -195,8 +315,67 @@ async fn main() -> Result<()> {
                     .action(ArgAction::Set)
                     .required(true)
                     .help("Name of the GitHub Actions workflow to present as a trace. This is typically a filename such as \"check.yaml\""))
+            .arg(
+                Arg::new("branch")
+                    .long("branch")
+                    .action(ArgAction::Set)
+                    .help("Only include runs on this head branch, for example \"main\""))
+            .arg(
+                Arg::new("event")
+                    .long("event")
+                    .action(ArgAction::Set)
+                    .help("Only include runs triggered by this event, for example \"push\""))
+            .arg(
+                Arg::new("status")
+                    .long("status")
+                    .action(ArgAction::Set)
+                    .help("Only include runs with this status or conclusion, for example \"failure\""))
+            .arg(
+                Arg::new("limit")
+                    .long("limit")
+                    .action(ArgAction::Set)
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("10")
+                    .help("Maximum number of runs to retrieve, paginating as needed"))
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .default_value("./action-hero.db")
+                    .help("Path to the local SQLite cache of runs, jobs, and steps"))
+            .arg(
+                Arg::new("offline")
+                    .long("offline")
+                    .action(ArgAction::SetTrue)
+                    .help("Rebuild traces purely from the local cache without calling the GitHub API"))
+            .arg(
+                Arg::new("detect-regressions")
+                    .long("detect-regressions")
+                    .action(ArgAction::SetTrue)
+                    .help("Flag steps in the most recent run whose duration has regressed against recent runs, exiting nonzero if any are found"))
+            .arg(
+                Arg::new("regression-multiple")
+                    .long("regression-multiple")
+                    .action(ArgAction::Set)
+                    .value_parser(clap::value_parser!(f64))
+                    .default_value("3")
+                    .help("Multiple of the median absolute deviation a step must exceed to count as a regression"))
             .get_matches();
 
+    // `serve` mode is long-lived and sources repositories from webhook
+    // payloads, so it bypasses the positional arguments entirely.
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        let listen = serve_matches
+            .get_one::<String>("listen")
+            .unwrap();
+        let db = dbctx::Database::open(matches.get_one::<String>("db-path").unwrap())?;
+        let (client, auth) = setup_api_client().await?;
+        let provider = telemetry::init_tracer()?;
+
+        serve::serve(listen, client, auth, provider, db).await?;
+        return Ok(());
+    }
+
     let repository = matches
         .get_one::<String>("repository")
         .unwrap()
@@ -217,29 +396,152 @@ async fn main() -> Result<()> {
 
     debug!(workflow);
 
-    let client = setup_api_client()?;
+    let db_path = matches
+        .get_one::<String>("db-path")
+        .unwrap();
+    let offline = matches.get_flag("offline");
+
+    let db = dbctx::Database::open(db_path)?;
+
+    let provider = telemetry::init_tracer()?;
+
+    // the most recent run, as (run object, jobs) — sourced either from the API
+    // (recording the delta into the cache as we go) or rebuilt from the cache
+    // in `--offline` mode.
+    let latest: Option<(Value, Vec<Value>)> = if offline {
+        // pick the newest run held locally for this repo/workflow and
+        // reconstruct it from the cache.
+        match db.run_ids(&owner, &repository, &workflow)?.first() {
+            Some(run_id) => {
+                debug!(run_id, "rebuilding trace from cache");
+                db.load_run(*run_id)?
+            }
+            None => {
+                println!("no runs cached at {}", db_path);
+                None
+            }
+        }
+    } else {
+        let (client, auth) = setup_api_client().await?;
+
+        let api = API {
+            client,
+            provider: auth,
+            owner: owner.clone(),
+            repository: repository.clone(),
+            workflow: workflow.clone(),
+        };
+
+        let query = RunQuery {
+            branch: matches.get_one::<String>("branch").cloned(),
+            event: matches.get_one::<String>("event").cloned(),
+            status: matches.get_one::<String>("status").cloned(),
+            limit: *matches.get_one::<usize>("limit").unwrap(),
+        };
+
+        let runs: Vec<String> = retrieve_workflow_runs(&api, &query).await?;
+
+        println!("runs: {:#?}", runs);
+
+        // fetch and record any run in the current (possibly filtered) window
+        // that isn't already cached. Gating on per-run presence rather than a
+        // single newest-id boundary means a filter surfacing an older matching
+        // run still gets backfilled, and widening `--limit` later backfills the
+        // runs the narrower window skipped.
+        for run_id in &runs {
+            let numeric: i64 = run_id.parse().unwrap_or_default();
+            if db.contains_run(numeric)? {
+                continue;
+            }
+            debug!(run_id, "fetching new run");
+            let run = retrieve_run(&api, run_id).await?;
+            let jobs = retrieve_run_jobs(&api, run_id).await?;
+            db.record_run(&api.owner, &api.repository, &api.workflow, &run, &jobs)?;
+        }
 
-    let api = API {
-        client,
-        owner,
-        repository,
-        workflow,
+        // display and trace the most recent run in the (possibly filtered) set.
+        match runs.first() {
+            Some(run_id) => {
+                debug!(run_id);
+                let numeric: i64 = run_id.parse().unwrap_or_default();
+                db.load_run(numeric)?
+            }
+            None => None,
+        }
     };
 
-    let runs: Vec<String> = retrieve_workflow_runs(&api).await?;
-
-    println!("runs: {:#?}", runs);
-
-    let run_id: &str = runs
-        .first()
-        .unwrap()
-        .as_ref();
-
-    debug!(run_id);
+    let detect_regressions = matches.get_flag("detect-regressions");
+    let mut regressions_found = false;
+
+    if let Some((run, jobs)) = latest {
+        display_job_steps(&jobs);
+
+        // optionally analyse how the most recent run's step durations compare
+        // against the recent successful history held in the cache.
+        let regressions = if detect_regressions {
+            let multiple = *matches.get_one::<f64>("regression-multiple").unwrap();
+            // analyse the run we are actually displaying and tracing, not
+            // merely the newest successful run in the cache: the two differ
+            // whenever the most recent run failed. The baseline is the cached
+            // successful history with that run removed so it can't compare
+            // against itself.
+            let run_id = run["id"].as_i64();
+            let mut history = successful_runs(&db, &owner, &repository, &workflow)?;
+            history.retain(|(cached, _)| cached["id"].as_i64() != run_id);
+            history.insert(0, (run.clone(), jobs.clone()));
+            let found = regression::detect(&history, multiple, MIN_REGRESSION_SAMPLES);
+            if found.is_empty() {
+                println!("no step-duration regressions detected");
+            } else {
+                regression::report(&found);
+                regressions_found = true;
+            }
+            found
+        } else {
+            Vec::new()
+        };
+
+        // now send the same run to OpenTelemetry as spans and traces, which is
+        // the behaviour the `about` string has always promised. Any regression
+        // verdict rides along as a span attribute.
+        telemetry::emit_run_trace(&provider, &run, &jobs, &regressions);
+    }
 
-    let jobs: Vec<Value> = retrieve_run_jobs(&api, &run_id).await?;
+    // flush and shut down the provider so all buffered spans are exported
+    // before we return.
+    provider.force_flush();
+    if let Err(error) = provider.shutdown() {
+        debug!(?error, "Failed to cleanly shut down tracer provider");
+    }
+    opentelemetry::global::shutdown_tracer_provider();
 
-    display_job_steps(&jobs);
+    // a nonzero exit code lets `--detect-regressions` gate a CI pipeline.
+    if regressions_found {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+/// Minimum number of historical runs a step must appear in before we are
+/// willing to emit a regression verdict for it.
+const MIN_REGRESSION_SAMPLES: usize = 5;
+
+/// Load the cached runs for this repo/workflow that concluded successfully,
+/// newest first, so they can serve as the regression baseline.
+fn successful_runs(
+    db: &dbctx::Database,
+    owner: &str,
+    repository: &str,
+    workflow: &str,
+) -> Result<Vec<(Value, Vec<Value>)>> {
+    let mut runs = Vec::new();
+    for run_id in db.run_ids(owner, repository, workflow)? {
+        if let Some((run, jobs)) = db.load_run(run_id)? {
+            if run["conclusion"].as_str() == Some("success") {
+                runs.push((run, jobs));
+            }
+        }
+    }
+    Ok(runs)
+}
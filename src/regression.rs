@@ -0,0 +1,117 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// A step whose duration in the most recent run has regressed well beyond its
+/// historical baseline.
+pub struct Regression {
+    pub job_name: String,
+    pub step_name: String,
+    pub latest: f64,
+    pub median: f64,
+    pub mad: f64,
+}
+
+/// Duration of a step in seconds, or `None` if either timestamp is missing or
+/// unparseable (for example a step that never ran).
+fn step_duration(step: &Value) -> Option<f64> {
+    let start = OffsetDateTime::parse(step["started_at"].as_str()?, &Rfc3339).ok()?;
+    let finish = OffsetDateTime::parse(step["completed_at"].as_str()?, &Rfc3339).ok()?;
+    Some((finish - start).as_seconds_f64())
+}
+
+/// Collect every step duration in a run, keyed by `job.name` + `step.name` so
+/// the same logical step can be matched across runs.
+fn run_durations(jobs: &[Value]) -> BTreeMap<(String, String), f64> {
+    let mut durations = BTreeMap::new();
+    for job in jobs {
+        let job_name = job["name"].as_str().unwrap_or_default().to_owned();
+        for step in job["steps"].as_array().into_iter().flatten() {
+            let step_name = step["name"].as_str().unwrap_or_default().to_owned();
+            if let Some(duration) = step_duration(step) {
+                durations.insert((job_name.clone(), step_name), duration);
+            }
+        }
+    }
+    durations
+}
+
+/// The median of a non-empty slice; the upper of the two middle values for an
+/// even count, which is adequate for a robust baseline.
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values[values.len() / 2]
+}
+
+/// The median absolute deviation: the median of each value's absolute distance
+/// from the sample median. Preferred over standard deviation so a single
+/// pathological run doesn't inflate the baseline.
+fn mad(values: &[f64], centre: f64) -> f64 {
+    let mut deviations: Vec<f64> = values.iter().map(|value| (value - centre).abs()).collect();
+    median(&mut deviations)
+}
+
+/// Detect step-duration regressions in the most recent of `runs` (expected to
+/// be ordered newest-first) against a baseline built from the remaining runs.
+///
+/// A step is flagged when its latest duration exceeds the historical median by
+/// more than `multiple` times the median absolute deviation. No verdict is
+/// emitted for a step with fewer than `min_samples` historical observations, so
+/// a short history can't produce spurious alarms.
+pub fn detect(runs: &[(Value, Vec<Value>)], multiple: f64, min_samples: usize) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+
+    let latest = match runs.first() {
+        Some((_, jobs)) => run_durations(jobs),
+        None => return regressions,
+    };
+
+    // gather the historical durations for each step across the older runs.
+    let mut history: BTreeMap<(String, String), Vec<f64>> = BTreeMap::new();
+    for (_, jobs) in &runs[1..] {
+        for (key, duration) in run_durations(jobs) {
+            history.entry(key).or_default().push(duration);
+        }
+    }
+
+    for (key, current) in latest {
+        let samples = match history.get(&key) {
+            Some(samples) if samples.len() >= min_samples => samples.clone(),
+            _ => continue,
+        };
+
+        let mut sorted = samples.clone();
+        let centre = median(&mut sorted);
+        let deviation = mad(&samples, centre);
+
+        if current > centre + multiple * deviation {
+            regressions.push(Regression {
+                job_name: key.0,
+                step_name: key.1,
+                latest: current,
+                median: centre,
+                mad: deviation,
+            });
+        }
+    }
+
+    regressions
+}
+
+/// Print the flagged regressions as a table for CI logs.
+pub fn report(regressions: &[Regression]) {
+    println!(
+        "{:<28} {:<32} {:>10} {:>10} {:>10}",
+        "job", "step", "latest(s)", "median(s)", "mad(s)"
+    );
+    for regression in regressions {
+        println!(
+            "{:<28} {:<32} {:>10.1} {:>10.1} {:>10.1}",
+            regression.job_name,
+            regression.step_name,
+            regression.latest,
+            regression.median,
+            regression.mad,
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{API, auth::TokenProvider, dbctx::Database, retrieve_run, retrieve_run_jobs, telemetry};
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use opentelemetry_sdk::trace::TracerProvider;
+use serde_json::Value;
+use sha2::Sha256;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Shared state handed to every webhook request: the authenticated API client,
+/// the tracer provider spans are emitted through, the local cache, and the
+/// shared secret used to verify delivery signatures.
+struct Context {
+    client: reqwest::Client,
+    auth: Arc<TokenProvider>,
+    provider: TracerProvider,
+    db: Mutex<Database>,
+    secret: String,
+}
+
+/// Run the long-lived webhook listener on `listen`, emitting a trace the moment
+/// a `workflow_run` completes rather than polling. Repositories are read from
+/// each event's payload, so a single listener can serve many repos.
+pub async fn serve(
+    listen: &str,
+    client: reqwest::Client,
+    auth: Arc<TokenProvider>,
+    provider: TracerProvider,
+    db: Database,
+) -> Result<()> {
+    let secret = std::env::var("GITHUB_WEBHOOK_SECRET")
+        .expect("GITHUB_WEBHOOK_SECRET environment variable not set");
+
+    let context = Arc::new(Context {
+        client,
+        auth,
+        provider,
+        db: Mutex::new(db),
+        secret,
+    });
+
+    let app = Router::new()
+        .route("/", post(handle_webhook))
+        .with_state(context);
+
+    debug!(listen, "starting webhook listener");
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Accept a GitHub webhook delivery, verify its signature, and dispatch
+/// completed `workflow_run` events to the tracing pipeline.
+async fn handle_webhook(
+    State(context): State<Arc<Context>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    // authenticity first: reject anything whose HMAC doesn't match before we
+    // even parse the payload.
+    let signature = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|value| value.to_str().ok());
+    if !verify_signature(&context.secret, &body, signature) {
+        warn!("rejected webhook with invalid signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(error) => {
+            warn!(?error, "failed to parse webhook payload");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    // we only act on a completed workflow_run; workflow_job deliveries are
+    // accepted (and acknowledged) but do not themselves drive a trace.
+    if event == "workflow_run" && payload["action"].as_str() == Some("completed") {
+        if let Err(error) = trace_delivery(&context, &payload).await {
+            warn!(?error, "failed to trace workflow run");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    StatusCode::OK
+}
+
+/// Re-run the existing retrieve/trace pipeline for the run named in a verified
+/// `workflow_run` delivery, reading owner/repo from the payload itself.
+async fn trace_delivery(context: &Context, payload: &Value) -> Result<()> {
+    let repo = &payload["repository"];
+    let owner = repo["owner"]["login"].as_str().unwrap_or_default().to_owned();
+    let repository = repo["name"].as_str().unwrap_or_default().to_owned();
+    // scope the cache entry to the workflow the run belongs to, read from the
+    // event payload rather than the (serve-mode absent) CLI argument. Use the
+    // workflow filename from `path` (e.g. ".github/workflows/check.yaml" ->
+    // "check.yaml") so it matches the identifier the polling/`--offline` paths
+    // key on, keeping the shared cache visible across both modes.
+    let workflow = payload["workflow_run"]["path"]
+        .as_str()
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or_default()
+        .to_owned();
+    let run_id = payload["workflow_run"]["id"]
+        .as_i64()
+        .unwrap_or_default()
+        .to_string();
+
+    debug!(owner, repository, run_id, "tracing completed workflow run");
+
+    let api = API {
+        client: context.client.clone(),
+        provider: context.auth.clone(),
+        owner,
+        repository,
+        workflow,
+    };
+
+    let run = retrieve_run(&api, &run_id).await?;
+    let jobs = retrieve_run_jobs(&api, &run_id).await?;
+    context
+        .db
+        .lock()
+        .unwrap()
+        .record_run(&api.owner, &api.repository, &api.workflow, &run, &jobs)?;
+    telemetry::emit_run_trace(&context.provider, &run, &jobs, &[]);
+    context.provider.force_flush();
+
+    Ok(())
+}
+
+/// Verify the `sha256=<hex>` signature GitHub sends against an HMAC-SHA256 of
+/// the raw request body keyed by the shared secret, comparing in constant time.
+fn verify_signature(secret: &str, body: &[u8], signature: Option<&str>) -> bool {
+    let signature = match signature.and_then(|value| value.strip_prefix("sha256=")) {
+        Some(signature) => signature,
+        None => return false,
+    };
+
+    let expected = match hex_decode(signature) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts a key of any length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Decode a lowercase hex string into bytes, returning `None` on any non-hex
+/// input. Kept local to avoid pulling in a dependency for a dozen bytes.
+fn hex_decode(input: &str) -> Option<Vec<u8>> {
+    if input.len() % 2 != 0 || !input.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use opentelemetry::trace::{
+    SpanBuilder, SpanKind, Status, TraceContextExt, Tracer, TracerProvider as _,
+};
+use opentelemetry::{KeyValue, global};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::TracerProvider;
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::debug;
+
+/// Name reported as the `service.name` resource attribute of every span we
+/// export. Trace backends group traces by this, so keep it stable.
+const SERVICE_NAME: &str = "action-hero";
+
+/// Build an OTLP tracer provider pointing at `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// (falling back to the collector default of `http://localhost:4317`). The
+/// provider is returned to the caller so it can be flushed and shut down
+/// before `main` returns; dropping it early would lose buffered spans.
+pub fn init_tracer() -> Result<TracerProvider> {
+    let mut exporter = opentelemetry_otlp::new_exporter().tonic();
+    if let Ok(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        debug!(?endpoint);
+        exporter = exporter.with_endpoint(endpoint);
+    }
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(
+            opentelemetry_sdk::trace::Config::default().with_resource(Resource::new(vec![
+                KeyValue::new("service.name", SERVICE_NAME),
+            ])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .context("Failed to install OTLP tracer pipeline")?;
+
+    global::set_tracer_provider(provider.clone());
+
+    Ok(provider)
+}
+
+/// Parse an RFC 3339 GitHub timestamp into a `SystemTime` suitable for the
+/// OpenTelemetry SDK's span start/end fields.
+fn parse_timestamp(value: &Value, field: &str) -> Option<SystemTime> {
+    let raw = value[field].as_str()?;
+    let parsed = OffsetDateTime::parse(raw, &Rfc3339).ok()?;
+    Some(SystemTime::from(parsed))
+}
+
+/// Emit a trace for a single workflow run: a root span covering the run,
+/// a child span per job, and a grandchild span per step.
+///
+/// GitHub timestamps only have second granularity, so two sibling jobs (or
+/// steps) that started in the same second would otherwise collapse onto the
+/// same instant and lose their ordering. We preserve it by nudging each
+/// successive sibling's start forward by its index in nanoseconds, which is
+/// far below GitHub's resolution and keeps the visual ordering intact.
+pub fn emit_run_trace(
+    provider: &TracerProvider,
+    run: &Value,
+    jobs: &[Value],
+    regressions: &[crate::regression::Regression],
+) {
+    let tracer = provider.tracer(SERVICE_NAME);
+
+    // steps flagged as duration regressions, so we can annotate their spans.
+    let regressed: std::collections::HashSet<(&str, &str)> = regressions
+        .iter()
+        .map(|r| (r.job_name.as_str(), r.step_name.as_str()))
+        .collect();
+
+    let run_id = run["id"].as_i64().unwrap_or_default();
+    let run_name = run["name"].as_str().unwrap_or("workflow run");
+
+    let run_start = parse_timestamp(run, "created_at").unwrap_or_else(SystemTime::now);
+    let run_finish = parse_timestamp(run, "updated_at").unwrap_or(run_start);
+
+    let root = tracer.build(
+        SpanBuilder::from_name(run_name.to_owned())
+            .with_kind(SpanKind::Server)
+            .with_start_time(run_start)
+            .with_attributes(vec![
+                KeyValue::new("github.run_id", run_id),
+                KeyValue::new("github.run_attempt", run["run_attempt"].as_i64().unwrap_or(1)),
+                KeyValue::new(
+                    "github.head_branch",
+                    run["head_branch"].as_str().unwrap_or("").to_owned(),
+                ),
+                KeyValue::new("github.event", run["event"].as_str().unwrap_or("").to_owned()),
+            ]),
+    );
+
+    let run_cx = opentelemetry::Context::current_with_span(root);
+
+    for (j, job) in jobs.iter().enumerate() {
+        let job_name = job["name"].as_str().unwrap_or("job").to_owned();
+        let job_start = parse_timestamp(job, "started_at")
+            .map(|t| t + Duration::from_nanos(j as u64))
+            .unwrap_or(run_start);
+        let job_finish = parse_timestamp(job, "completed_at").unwrap_or(job_start);
+
+        let mut job_span = tracer.build_with_context(
+            SpanBuilder::from_name(job_name.clone())
+                .with_kind(SpanKind::Internal)
+                .with_start_time(job_start)
+                .with_attributes(vec![
+                    KeyValue::new("github.run_id", run_id),
+                    KeyValue::new("github.job.name", job_name.clone()),
+                    KeyValue::new(
+                        "runner.name",
+                        job["runner_name"].as_str().unwrap_or("").to_owned(),
+                    ),
+                ]),
+            &run_cx,
+        );
+        set_conclusion_status(&mut job_span, job["conclusion"].as_str());
+
+        let job_cx = run_cx.with_span(job_span);
+
+        for (s, step) in job["steps"].as_array().into_iter().flatten().enumerate() {
+            let step_name = step["name"].as_str().unwrap_or("step").to_owned();
+            let step_regressed = regressed.contains(&(job_name.as_str(), step_name.as_str()));
+            let step_start = parse_timestamp(step, "started_at")
+                .map(|t| t + Duration::from_nanos(s as u64))
+                .unwrap_or(job_start);
+            let step_finish = parse_timestamp(step, "completed_at").unwrap_or(step_start);
+
+            let mut step_span = tracer.build_with_context(
+                SpanBuilder::from_name(step_name.clone())
+                    .with_kind(SpanKind::Internal)
+                    .with_start_time(step_start)
+                    .with_attributes(vec![
+                        KeyValue::new("github.step.name", step_name),
+                        KeyValue::new(
+                            "github.step.number",
+                            step["number"].as_i64().unwrap_or_default(),
+                        ),
+                    ]),
+                &job_cx,
+            );
+            set_conclusion_status(&mut step_span, step["conclusion"].as_str());
+            if step_regressed {
+                step_span.set_attribute(KeyValue::new("github.step.regression", true));
+            }
+            step_span.end_with_timestamp(step_finish);
+        }
+
+        job_cx.span().end_with_timestamp(job_finish);
+    }
+
+    run_cx.span().end_with_timestamp(run_finish);
+}
+
+/// Translate a GitHub `conclusion` onto an OpenTelemetry span status: anything
+/// other than `success` or `skipped` is treated as an error so failures stand
+/// out in the trace viewer.
+fn set_conclusion_status<S: opentelemetry::trace::Span>(span: &mut S, conclusion: Option<&str>) {
+    match conclusion {
+        Some("success") | Some("skipped") => span.set_status(Status::Ok),
+        Some(other) => span.set_status(Status::error(other.to_owned())),
+        None => {}
+    }
+    if let Some(conclusion) = conclusion {
+        span.set_attribute(KeyValue::new("github.conclusion", conclusion.to_owned()));
+    }
+}